@@ -4,14 +4,17 @@ use enclose::enc;
 use gloo::timers::callback::Timeout;
 use log::*;
 use reqwasm::http::Request;
-use runner::{RunResult, Runner};
+use runner::{RunResult, Runner, TestReport};
+use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlElement;
 use yew::prelude::*;
 use yew_agent::Bridged;
 
 use crate::runner;
 
 static EXAMPLES: &[&str] = &["hello-world", "factorial", "fibonacci", "speed-test"];
+const DEFAULT_TIMEOUT_SECS: f64 = 5.0;
 
 #[function_component(Header)]
 fn header() -> Html {
@@ -26,44 +29,105 @@ pub fn app() -> Html {
 
     let source = use_state(|| "".to_string());
     let output = use_state(|| "".to_string());
+    let result_value = use_state(|| None as Option<String>);
+    let test_report = use_state(|| None as Option<TestReport>);
+    let coverage = use_state(|| None as Option<(usize, usize, Vec<usize>)>);
+    let show_junit = use_state(|| false);
     let is_loading = use_state(|| false);
     let is_error = use_state(|| false);
     let timeout_handle = use_ref(|| None);
+    let watchdog_handle = use_ref(|| None);
+    // Mirrors `is_loading` outside of the `UseStateHandle` so closures captured at render time
+    // (e.g. the watchdog below) can read the *current* value instead of a stale snapshot from
+    // the render that created them -- a `UseStateHandle` always derefs to the value it closed
+    // over, even after calling `.set()` on it.
+    let is_loading_flag = use_ref(|| false);
+    let timeout_limit_secs = use_state(|| DEFAULT_TIMEOUT_SECS);
     let examples_dropdown_open = use_state(|| false);
+    let source_gutter_ref = use_node_ref();
 
-    let report_output = Rc::new(enc!((output) move |new_output: String| {
+    let report_output = Rc::new(enc!((output, is_loading, is_loading_flag) move |new_output: String| {
+        if new_output.contains("[INFO] Execution finished") || new_output.contains("[INFO] execution cancelled") {
+            *is_loading_flag.borrow_mut() = false;
+            is_loading.set(false);
+        }
         output.set(new_output);
     }));
 
-    let report_errors = Rc::new(enc!((output, is_error) move |errors_string: String| {
+    let report_value = Rc::new(enc!((result_value) move |value: String| {
+        result_value.set(Some(value));
+    }));
+
+    let report_test_report = Rc::new(enc!((test_report) move |report: TestReport| {
+        test_report.set(Some(report));
+    }));
+
+    let report_coverage = Rc::new(enc!((coverage) move |covered: usize, total: usize, uncovered_lines: Vec<usize>| {
+        coverage.set(Some((covered, total, uncovered_lines)));
+    }));
+
+    let report_errors = Rc::new(enc!((output, is_error, is_loading, is_loading_flag) move |errors_string: String| {
         output.set(errors_string);
         is_error.set(true);
+        *is_loading_flag.borrow_mut() = false;
+        is_loading.set(false);
     }));
 
     let callback = Callback::from(
-        enc!((report_output, report_errors) move |result: RunResult| {
+        enc!((report_output, report_value, report_test_report, report_coverage, report_errors) move |result: RunResult| {
             match result {
                 RunResult::Stdout(stdout) => report_output(stdout),
+                RunResult::Value(value) => report_value(value),
+                RunResult::TestReport(report) => report_test_report(report),
+                RunResult::Coverage { covered, total, uncovered_lines } => report_coverage(covered, total, uncovered_lines),
                 RunResult::Error(err) => report_errors(err),
             }
         }),
     );
-    let runner_handle = use_ref(|| Runner::bridge(callback));
+    let runner_handle = use_ref(|| Runner::bridge(callback.clone()));
 
     let handle_run = Callback::from(
-        enc!((source, output, is_loading, is_error, timeout_handle) move |_| {
+        enc!((source, output, result_value, test_report, coverage, is_loading, is_loading_flag, is_error,
+              timeout_handle, watchdog_handle, timeout_limit_secs, runner_handle, callback) move |_| {
             output.set("".to_string());
+            result_value.set(None);
+            test_report.set(None);
+            coverage.set(None);
+            *is_loading_flag.borrow_mut() = true;
             is_loading.set(true);
             is_error.set(false);
 
+            let timeout_secs = *timeout_limit_secs;
+
             let handle = Timeout::new(
                 0,
-                enc!((source, runner_handle, is_loading) move || {
-                    runner_handle.borrow_mut().send(runner::Request::ExecuteCode(source.to_string()));
-                    is_loading.set(false);
+                enc!((source, runner_handle) move || {
+                    runner_handle
+                        .borrow_mut()
+                        .send(runner::Request::EvalLine { source: source.to_string(), timeout_secs });
                 }),
             );
             *timeout_handle.borrow_mut() = Some(handle);
+
+            // Safety net for when the worker doesn't come back at all (e.g. the in-VM time
+            // limit check itself never runs): tear down and respawn the bridge so the "Run"
+            // button can recover instead of staying stuck forever.
+            let watchdog = Timeout::new(
+                (timeout_secs * 1000.0) as u32 + 500,
+                enc!((output, is_error, is_loading, is_loading_flag, runner_handle, callback) move || {
+                    if *is_loading_flag.borrow() {
+                        *runner_handle.borrow_mut() = Runner::bridge(callback.clone());
+                        *is_loading_flag.borrow_mut() = false;
+                        is_loading.set(false);
+                        is_error.set(true);
+                        output.set(format!(
+                            "[INFO] execution did not respond within {:.1}s; worker restarted\n",
+                            timeout_secs
+                        ));
+                    }
+                }),
+            );
+            *watchdog_handle.borrow_mut() = Some(watchdog);
         }),
     );
 
@@ -76,6 +140,19 @@ pub fn app() -> Html {
         examples_dropdown_open.set(!*examples_dropdown_open);
     }));
 
+    let toggle_junit = Callback::from(enc!((show_junit) move |_| {
+        show_junit.set(!*show_junit);
+    }));
+
+    // Keeps the coverage gutter's line numbers aligned with the textarea as the user scrolls
+    // through a longer script.
+    let sync_gutter_scroll = Callback::from(enc!((source_gutter_ref) move |event: Event| {
+        let textarea = event.target_dyn_into::<HtmlElement>();
+        if let (Some(textarea), Some(gutter)) = (textarea, source_gutter_ref.cast::<HtmlElement>()) {
+            gutter.set_scroll_top(textarea.scroll_top());
+        }
+    }));
+
     let load_example = Rc::new(Callback::from(enc!(
         (source) move |name| {
             info!("loading example {}", name);
@@ -102,6 +179,28 @@ pub fn app() -> Html {
                     >{ "Run" }</button>
                 </div>
 
+                <div class="column">
+                    <div class="field has-addons">
+                        <div class="control">
+                            <a class="button is-static">{ "Timeout (s)" }</a>
+                        </div>
+                        <div class="control">
+                            <input
+                                class="input"
+                                type="number"
+                                min="0.1"
+                                step="0.1"
+                                value={timeout_limit_secs.to_string()}
+                                oninput={Callback::from(enc!((timeout_limit_secs) move |ev: InputData| {
+                                    if let Ok(secs) = ev.value.parse() {
+                                        timeout_limit_secs.set(secs);
+                                    }
+                                }))}
+                            />
+                        </div>
+                    </div>
+                </div>
+
                 <div class="column">
                     <div class={format!("dropdown {}", if *examples_dropdown_open { "is-active" } else { "" })}>
                         <button class="button dropdown-trigger" onclick={toggle_dropdown}>{ "Example scripts" }</button>
@@ -122,15 +221,39 @@ pub fn app() -> Html {
 
             <div class="columns input-area">
                 <div class="column">
-                    <div class="control">
+                    <div class="control source-with-gutter">
+                        if let Some((_, _, uncovered_lines)) = (*coverage).clone() {
+                            <div class="coverage-gutter" ref={source_gutter_ref.clone()}>
+                                { for (*source).split('\n').enumerate().map(|(i, _)| {
+                                    let line_no = i + 1;
+                                    let uncovered = uncovered_lines.contains(&line_no);
+                                    html! {
+                                        <div class={classes!("coverage-gutter-line", uncovered.then(|| "coverage-gutter-line-uncovered"))}>
+                                            { line_no }
+                                        </div>
+                                    }
+                                }) }
+                            </div>
+                        }
                         <textarea
                             class="textarea"
                             placeholder="Source code here..."
                             spellcheck="false"
                             value={(*source).clone()}
                             oninput={Callback::from(enc!((source) move |ev: InputData| source.set(ev.value)))}
+                            onscroll={sync_gutter_scroll}
                         />
                     </div>
+                    if let Some((covered, total, uncovered_lines)) = (*coverage).clone() {
+                        <div class="coverage-summary">
+                            { format!("Coverage: {}/{} lines executed", covered, total) }
+                            if !uncovered_lines.is_empty() {
+                                <span class="coverage-uncovered">
+                                    { format!(" (not run: {})", uncovered_lines.iter().map(|line| line.to_string()).collect::<Vec<_>>().join(", ")) }
+                                </span>
+                            }
+                        </div>
+                    }
                 </div>
 
                 <div class="column">
@@ -141,6 +264,26 @@ pub fn app() -> Html {
                             spellcheck="false"
                             value={(*output).clone()}
                         />
+                        if let Some(value) = (*result_value).clone() {
+                            <div class="result-value">{ format!("=> {}", value) }</div>
+                        }
+                        if let Some(report) = (*test_report).clone() {
+                            <div class="test-report">
+                                <button class="button is-small" onclick={toggle_junit}>
+                                    { if *show_junit { "View as plain text" } else { "View as JUnit XML" } }
+                                </button>
+                                <pre>{
+                                    if *show_junit {
+                                        report.to_junit_xml()
+                                    } else {
+                                        report.cases.iter().map(|case| match &case.failure {
+                                            Some(failure) => format!("FAILED {} ({:.3}s): {} (line {})\n", case.name, case.time, failure.message, failure.line),
+                                            None => format!("ok {} ({:.3}s)\n", case.name, case.time),
+                                        }).collect::<String>()
+                                    }
+                                }</pre>
+                            </div>
+                        }
                     </div>
                 </div>
             </div>
@@ -1,20 +1,33 @@
 //! Yew Agent for running code (in a web worker).
+//!
+//! Requires `Vm::set_time_limit`/`clear_coverage`/`covered_lines`/`last_value`,
+//! `InterpretResult::Cancelled`, `Codegen::executable_lines`, and
+//! `BuiltinVars::add_vm_native_fn`/`Vm::call_value`/`Vm::runtime_error` from the companion `ella`
+//! crates (chunk0-3/chunk0-4/chunk0-5). This source tree ships without a `Cargo.toml`/lockfile to
+//! pin that crate, so landing this series depends on those additions already being merged and
+//! published upstream -- confirm that before relying on this file compiling.
 
-use std::rc::Rc;
+use std::collections::HashSet;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
 
 use ella::builtin_functions;
+use ella_parser::ast::Stmt;
 use ella_parser::parser::Parser;
-use ella_passes::resolve::Resolver;
-use ella_passes::type_checker::TypeChecker;
+use ella_passes::resolve::{ResolveResult, Resolver};
+use ella_passes::type_checker::{TypeCheckResult, TypeChecker};
 use ella_source::Source;
 use ella_value::Value;
 use ella_value::{BuiltinType, BuiltinVars, UniqueType};
 use ella_vm::codegen::Codegen;
 use ella_vm::vm::{InterpretResult, Vm};
 use enclose::enc;
+use reqwasm::http::Request as HttpRequest;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::spawn_local;
 use yew::worker::*;
 
 #[wasm_bindgen(
@@ -29,148 +42,526 @@ fn native_clock(_args: &mut [Value]) -> Value {
     Value::Number(time)
 }
 
-pub fn run(
-    source: Rc<String>,
-    report_output: Rc<impl Fn(String) + 'static>,
-    report_errors: Rc<impl Fn(String)>,
-) {
-    let start = js_clock();
+/// A runtime error (such as a failed `assert`/`assert_eq`), scoped to whichever `test(...)` call
+/// was running when it happened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestFailure {
+    pub message: String,
+    pub line: usize,
+}
+
+/// The outcome of a single `test(name, fn)` invocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestCase {
+    pub name: String,
+    pub time: f64,
+    pub failure: Option<TestFailure>,
+}
+
+/// All `test(...)` outcomes collected over one [`eval_with_imports`] call, modeled on a JUnit
+/// test suite.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TestReport {
+    pub cases: Vec<TestCase>,
+}
+
+impl TestReport {
+    fn is_empty(&self) -> bool {
+        self.cases.is_empty()
+    }
+
+    /// Renders the report as JUnit XML, with one `<testcase>` per `test(...)` call and a nested
+    /// `<failure>` for each one that ended in a runtime error (such as a failed `assert`/`assert_eq`).
+    pub fn to_junit_xml(&self) -> String {
+        let total_time: f64 = self.cases.iter().map(|case| case.time).sum();
+        let failures = self.cases.iter().filter(|case| case.failure.is_some()).count();
+
+        let mut xml = format!(
+            "<testsuite name=\"owllang-playground\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+            self.cases.len(),
+            failures,
+            total_time
+        );
+        for case in &self.cases {
+            xml += &format!(
+                "  <testcase name=\"{}\" time=\"{:.3}\">\n",
+                escape_xml(&case.name),
+                case.time
+            );
+            if let Some(failure) = &case.failure {
+                xml += &format!(
+                    "    <failure message=\"{}\">line {}</failure>\n",
+                    escape_xml(&failure.message),
+                    failure.line
+                );
+            }
+            xml += "  </testcase>\n";
+        }
+        xml += "</testsuite>\n";
+        xml
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn native_assert(vm: &mut Vm, args: &mut [Value]) -> Value {
+    if matches!(&args[0], Value::Bool(true)) {
+        Value::Bool(true)
+    } else {
+        vm.runtime_error("assertion failed".to_string())
+    }
+}
+
+fn native_assert_eq(vm: &mut Vm, args: &mut [Value]) -> Value {
+    let (lhs, rhs) = (&args[0], &args[1]);
+    if lhs == rhs {
+        Value::Bool(true)
+    } else {
+        vm.runtime_error(format!("assertion failed: `{}` != `{}`", lhs, rhs))
+    }
+}
+
+/// Where the currently-running snippet's stdout goes. Swapped out at the start of every
+/// [`eval_with_imports`] call so the `println` builtin (loaded once into the session's `Vm`)
+/// always reports to whoever is asking for the current evaluation's output.
+struct OutputSink {
+    buffer: String,
+    report_output: Weak<dyn Fn(String)>,
+}
 
-    let source = source.as_str().into();
-    let mut builtin_vars = BuiltinVars::new();
+/// A long-lived REPL session: the resolved/type-checked globals and the `Vm` that holds them,
+/// kept alive across submissions so that variables and functions defined in one snippet are
+/// still visible in the next one.
+pub struct Session {
+    resolve_result: Option<ResolveResult>,
+    type_check_result: Option<TypeCheckResult>,
+    vm: Vm,
+    output_sink: Rc<RefCell<OutputSink>>,
+    test_report: Rc<RefCell<TestReport>>,
+}
 
-    let output = Rc::new(RefCell::new(String::new()));
-    let report_output = Rc::downgrade(&report_output);
+impl Session {
+    fn new() -> Self {
+        let mut builtin_vars = BuiltinVars::new();
 
-    let native_println = Box::leak(Box::new(
-        enc!((output, report_output) move |args: &mut [Value]| {
+        let output_sink = Rc::new(RefCell::new(OutputSink {
+            buffer: String::new(),
+            report_output: Weak::<fn(String)>::new(),
+        }));
+
+        let native_println = Box::leak(Box::new(enc!((output_sink) move |args: &mut [Value]| {
             let arg = &args[0];
-            *output.borrow_mut() += &format!("[STDOUT] {}\n", arg);
+            let mut sink = output_sink.borrow_mut();
+            sink.buffer += &format!("[STDOUT] {}\n", arg);
 
-            if let Some(report_output) = report_output.upgrade() {
-                report_output(output.borrow().to_string())
+            if let Some(report_output) = sink.report_output.upgrade() {
+                report_output(sink.buffer.clone())
             }
             Value::Bool(true)
-        }),
-    ));
-    builtin_vars.add_native_fn(
-        "println",
-        native_println,
-        1,
-        BuiltinType::Fn {
-            params: vec![UniqueType::Any],
-            ret: Box::new(BuiltinType::Bool.into()),
-        }
-        .into(),
-    );
-    builtin_vars.add_native_fn(
-        "is_nan",
-        &builtin_functions::is_nan,
-        1,
-        BuiltinType::Fn {
-            params: vec![BuiltinType::Number.into()],
-            ret: Box::new(BuiltinType::Bool.into()),
-        }
-        .into(),
-    );
-    builtin_vars.add_native_fn(
-        "parse_number",
-        &builtin_functions::parse_number,
-        1,
-        BuiltinType::Fn {
-            params: vec![UniqueType::Any],
-            ret: Box::new(BuiltinType::Number.into()),
-        }
-        .into(),
-    );
-    builtin_vars.add_native_fn(
-        "clock",
-        &native_clock,
-        0,
-        BuiltinType::Fn {
-            params: Vec::new(),
-            ret: Box::new(BuiltinType::Number.into()),
-        }
-        .into(),
-    );
-    builtin_vars.add_native_fn(
-        "str",
-        &builtin_functions::str,
-        1,
-        BuiltinType::Fn {
-            params: vec![UniqueType::Any],
-            ret: Box::new(BuiltinType::String.into()),
-        }
-        .into(),
-    );
+        })));
+        builtin_vars.add_native_fn(
+            "println",
+            native_println,
+            1,
+            BuiltinType::Fn {
+                params: vec![UniqueType::Any],
+                ret: Box::new(BuiltinType::Bool.into()),
+            }
+            .into(),
+        );
+        builtin_vars.add_native_fn(
+            "is_nan",
+            &builtin_functions::is_nan,
+            1,
+            BuiltinType::Fn {
+                params: vec![BuiltinType::Number.into()],
+                ret: Box::new(BuiltinType::Bool.into()),
+            }
+            .into(),
+        );
+        builtin_vars.add_native_fn(
+            "parse_number",
+            &builtin_functions::parse_number,
+            1,
+            BuiltinType::Fn {
+                params: vec![UniqueType::Any],
+                ret: Box::new(BuiltinType::Number.into()),
+            }
+            .into(),
+        );
+        builtin_vars.add_native_fn(
+            "clock",
+            &native_clock,
+            0,
+            BuiltinType::Fn {
+                params: Vec::new(),
+                ret: Box::new(BuiltinType::Number.into()),
+            }
+            .into(),
+        );
+        builtin_vars.add_native_fn(
+            "str",
+            &builtin_functions::str,
+            1,
+            BuiltinType::Fn {
+                params: vec![UniqueType::Any],
+                ret: Box::new(BuiltinType::String.into()),
+            }
+            .into(),
+        );
+        builtin_vars.add_vm_native_fn(
+            "assert",
+            &native_assert,
+            1,
+            BuiltinType::Fn {
+                params: vec![BuiltinType::Bool.into()],
+                ret: Box::new(BuiltinType::Bool.into()),
+            }
+            .into(),
+        );
+        builtin_vars.add_vm_native_fn(
+            "assert_eq",
+            &native_assert_eq,
+            2,
+            BuiltinType::Fn {
+                params: vec![UniqueType::Any, UniqueType::Any],
+                ret: Box::new(BuiltinType::Bool.into()),
+            }
+            .into(),
+        );
+
+        let test_report = Rc::new(RefCell::new(TestReport::default()));
+        let native_test = Box::leak(Box::new(enc!((test_report) move |vm: &mut Vm, args: &mut [Value]| {
+            let name = format!("{}", args[0]);
+            let test_fn = args[1].clone();
 
-    let dummy_source: Source = "".into();
-    let mut resolver = Resolver::new(dummy_source.clone());
-    resolver.resolve_builtin_vars(&builtin_vars);
-    let mut resolve_result = resolver.into_resolve_result();
+            let start = js_clock();
+            // `call_value` is a catching call boundary: a `runtime_error` raised by `assert`/
+            // `assert_eq` inside `test_fn` unwinds back to here as `InterpretResult::RuntimeError`
+            // without leaving the VM's call/value stack unbalanced, so the next `test(...)` (or
+            // the rest of the global chunk) runs against a clean VM rather than a poisoned one.
+            let result = vm.call_value(&test_fn, &mut []);
+            let time = js_clock() - start;
+
+            let failure = match result {
+                InterpretResult::RuntimeError { message, line } => Some(TestFailure { message, line }),
+                _ => None,
+            };
+            test_report.borrow_mut().cases.push(TestCase { name, time, failure });
+            Value::Bool(true)
+        })));
+        builtin_vars.add_vm_native_fn(
+            "test",
+            native_test,
+            2,
+            BuiltinType::Fn {
+                params: vec![BuiltinType::String.into(), UniqueType::Any],
+                ret: Box::new(BuiltinType::Bool.into()),
+            }
+            .into(),
+        );
 
-    let mut type_checker = TypeChecker::new(&resolve_result, dummy_source.clone());
-    type_checker.type_check_builtin_vars(&builtin_vars);
-    let mut type_check_result = type_checker.into_type_check_result();
+        let dummy_source: Source = "".into();
+        let mut resolver = Resolver::new(dummy_source.clone());
+        resolver.resolve_builtin_vars(&builtin_vars);
+        let resolve_result = resolver.into_resolve_result();
 
-    let mut vm = Vm::new(&builtin_vars);
-    let mut codegen = Codegen::new("<global>".to_string(), &resolve_result, &type_check_result, &source);
-    codegen.codegen_builtin_vars(&builtin_vars);
-    vm.interpret(codegen.into_inner_chunk()); // load built in functions into memory
+        let mut type_checker = TypeChecker::new(&resolve_result, dummy_source.clone());
+        type_checker.type_check_builtin_vars(&builtin_vars);
+        let type_check_result = type_checker.into_type_check_result();
 
-    let mut parser = Parser::new(&source);
+        let mut vm = Vm::new(&builtin_vars);
+        let mut codegen = Codegen::new("<global>".to_string(), &resolve_result, &type_check_result, &dummy_source);
+        codegen.codegen_builtin_vars(&builtin_vars);
+        vm.interpret(codegen.into_inner_chunk()); // load built in functions into memory
+
+        Self {
+            resolve_result: Some(resolve_result),
+            type_check_result: Some(type_check_result),
+            vm,
+            output_sink,
+            test_report,
+        }
+    }
+}
+
+/// Either the module parsed/type-checked with errors (the formatted source diagnostics), or it
+/// ran, producing an `InterpretResult`, the set of lines it could have executed, and whether its
+/// last top-level statement was an expression (the only case that leaves a value on the stack).
+enum ModuleOutcome {
+    Errors(String),
+    Ran { result: InterpretResult, executable_lines: HashSet<usize>, ends_in_expr_stmt: bool },
+}
+
+/// Resolves, type-checks, codegens and interprets one module's source against `session`,
+/// threading the session's accumulated `ResolveResult`/`TypeCheckResult` through so the module's
+/// top-level definitions join whatever earlier modules (or REPL submissions) already defined.
+fn compile_module(session: &mut Session, source: &Source, timeout_secs: f64) -> ModuleOutcome {
+    let mut parser = Parser::new(source);
     let ast = parser.parse_program();
+    // `session.vm` is long-lived (the REPL session survives across submissions), so
+    // `vm.last_value()` alone can't tell a trailing expression in *this* chunk apart from a value
+    // left over from an earlier one -- only an `ExprStmt` in last position actually pushes one.
+    let ends_in_expr_stmt = matches!(ast.stmts.last(), Some(Stmt::ExprStmt(_)));
 
-    let mut resolver = Resolver::new_with_existing_resolve_result(source.clone(), resolve_result);
+    let mut resolver = Resolver::new_with_existing_resolve_result(
+        source.clone(),
+        session.resolve_result.take().expect("resolve_result always populated between calls"),
+    );
     resolver.resolve_top_level(&ast);
-    resolve_result = resolver.into_resolve_result();
+    session.resolve_result = Some(resolver.into_resolve_result());
 
-    let mut type_checker =
-        TypeChecker::new_with_type_check_result(&resolve_result, source.clone(), type_check_result);
+    let resolve_result = session.resolve_result.as_ref().unwrap();
+    let mut type_checker = TypeChecker::new_with_type_check_result(
+        resolve_result,
+        source.clone(),
+        session
+            .type_check_result
+            .take()
+            .expect("type_check_result always populated between calls"),
+    );
     type_checker.type_check_global(&ast);
-    type_check_result = type_checker.into_type_check_result();
-    let _ = type_check_result;
+    session.type_check_result = Some(type_checker.into_type_check_result());
+
+    if !source.has_no_errors() {
+        return ModuleOutcome::Errors(format!("{}", source));
+    }
+
+    // `ast` still contains this module's own `import` statements (`gather_modules` only reads
+    // them, to build the fetch order -- the module text handed to the compiler here is
+    // untouched). `Codegen`/`Resolver` treat `import` as a declaration resolved ahead of time,
+    // not a runtime statement, so it emits no bytecode here; each imported module's side effects
+    // run exactly once, when `compile_module` interprets *its own* entry in `order`.
+    let type_check_result = session.type_check_result.as_ref().unwrap();
+    let mut codegen = Codegen::new("<global>".to_string(), resolve_result, type_check_result, source);
+    codegen.codegen_function(&ast);
+
+    let executable_lines = codegen.executable_lines();
+    let chunk = codegen.into_inner_chunk();
+    session.vm.clear_coverage();
+    session.vm.set_time_limit(timeout_secs);
+    let result = session.vm.interpret(chunk);
 
-    if source.has_no_errors() {
-        let mut codegen = Codegen::new("<global>".to_string(), &resolve_result, &type_check_result, &source);
+    ModuleOutcome::Ran { result, executable_lines, ends_in_expr_stmt }
+}
 
-        codegen.codegen_function(&ast);
+/// Strips the leading `./` a specifier may be written with, so `"./util"` and `"util"` resolve
+/// to the same module identity instead of being treated as two distinct imports.
+fn normalize_specifier(specifier: &str) -> String {
+    specifier.trim_start_matches("./").to_string()
+}
 
-        let chunk = codegen.into_inner_chunk();
-        let result = vm.interpret(chunk);
+/// Fetches an imported module's source, probing `examples/<name>` then `examples/<name>.hoot`
+/// the way Deno's sloppy-imports feature resolves specifiers without an explicit extension.
+async fn fetch_module(specifier: &str) -> Result<String, String> {
+    let name = normalize_specifier(specifier);
+    for candidate in [name.clone(), format!("{}.hoot", name)] {
+        let url = format!("examples/{}", candidate);
+        let res = HttpRequest::get(&url)
+            .send()
+            .await
+            .map_err(|err| format!("could not fetch import \"{}\": {}", specifier, err))?;
+        if res.status() == 200 {
+            return res
+                .text()
+                .await
+                .map_err(|err| format!("could not read import \"{}\": {}", specifier, err));
+        }
+    }
+    Err(format!(
+        "import \"{}\" not found (tried \"{}\" and \"{}.hoot\" in examples/)",
+        specifier, name, name
+    ))
+}
 
-        if let InterpretResult::RuntimeError { message, line } = result {
-            *output.borrow_mut() += &format!("runtime error: {}\n   --> repl:{}\n", message, line);
-            if let Some(report_output) = report_output.upgrade() {
-                report_output(output.borrow().to_string());
+/// Depth-first walks the `import` graph starting at `entry_source`, fetching every imported
+/// module and returning module sources ordered so each one comes after all of its own imports
+/// (dependencies first, the entry module last) -- the order top-level definitions need to be
+/// resolved and interpreted in so imported globals exist before the module that uses them runs.
+///
+/// `specifier`, and the `visiting`/`visited` sets keyed on it, always hold the *normalized* path
+/// (see [`normalize_specifier`]) so `"./util"` and `"util"` are recognized as the same module
+/// instead of being fetched and interpreted twice.
+fn gather_modules<'a>(
+    specifier: Option<String>,
+    source: String,
+    visiting: &'a mut Vec<String>,
+    visited: &'a mut HashSet<String>,
+    order: &'a mut Vec<String>,
+) -> Pin<Box<dyn Future<Output = Result<(), String>> + 'a>> {
+    Box::pin(async move {
+        if let Some(specifier) = &specifier {
+            if visited.contains(specifier) {
+                return Ok(());
+            }
+            if visiting.contains(specifier) {
+                return Err(format!("import cycle detected at \"{}\"", specifier));
             }
+            visiting.push(specifier.clone());
         }
 
-        let end = js_clock();
-        *output.borrow_mut() +=
-            &format!("[INFO] Execution finished in {:.3} seconds\n", end - start);
-        if let Some(report_output) = report_output.upgrade() {
-            report_output(output.borrow().to_string());
+        let mut parser = Parser::new(&Source::from(source.as_str()));
+        let ast = parser.parse_program();
+        for import_specifier in ast.imports() {
+            let resolved = normalize_specifier(&import_specifier);
+            if visited.contains(&resolved) {
+                continue;
+            }
+            let module_source = fetch_module(&import_specifier).await?;
+            gather_modules(Some(resolved), module_source, visiting, visited, order).await?;
         }
-    } else {
-        let errors_string = format!("{}", source);
-        report_errors(errors_string);
+
+        if let Some(specifier) = specifier {
+            visiting.retain(|s| s != &specifier);
+            visited.insert(specifier);
+        }
+        order.push(source);
+        Ok(())
+    })
+}
+
+/// Runs a snippet against `session`, following any `import "./name"` statements (fetched from
+/// `examples/`) before the entry module itself, so imported top-level definitions are already in
+/// scope when the entry module runs. Resolving imports is async, so this drives the whole
+/// session mutation from an async context -- callers use `spawn_local` to kick it off.
+pub async fn eval_with_imports(
+    session: Rc<RefCell<Session>>,
+    source: Rc<String>,
+    timeout_secs: f64,
+    report_output: Rc<dyn Fn(String)>,
+    report_value: Rc<impl Fn(String)>,
+    report_test_report: Rc<impl Fn(TestReport)>,
+    report_coverage: Rc<impl Fn(usize, usize, Vec<usize>)>,
+    report_errors: Rc<impl Fn(String)>,
+) {
+    let start = js_clock();
+
+    let mut visiting = Vec::new();
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    if let Err(err) = gather_modules(None, source.to_string(), &mut visiting, &mut visited, &mut order).await {
+        report_errors(err);
+        return;
+    }
+
+    let mut session = session.borrow_mut();
+
+    {
+        let mut sink = session.output_sink.borrow_mut();
+        sink.buffer.clear();
+        sink.report_output = Rc::downgrade(&report_output);
+    }
+    session.test_report.borrow_mut().cases.clear();
+
+    let last = order.len() - 1;
+    for (i, module_source) in order.into_iter().enumerate() {
+        let module_source: Source = module_source.as_str().into();
+        let outcome = compile_module(&mut session, &module_source, timeout_secs);
+
+        let is_entry = i == last;
+        match outcome {
+            ModuleOutcome::Errors(errors_string) => {
+                report_errors(errors_string);
+                return;
+            }
+            ModuleOutcome::Ran { result, executable_lines, ends_in_expr_stmt } => match result {
+                InterpretResult::RuntimeError { message, line } => {
+                    let end = js_clock();
+                    let mut sink = session.output_sink.borrow_mut();
+                    sink.buffer += &format!("runtime error: {}\n   --> repl:{}\n", message, line);
+                    // Include the finished marker here too -- the UI clears its loading state by
+                    // watching for it, so without it a runtime error looks like a hung worker and
+                    // gets "recovered" by the watchdog, clobbering this message.
+                    sink.buffer += &format!("[INFO] Execution finished in {:.3} seconds\n", end - start);
+                    if let Some(report_output) = sink.report_output.upgrade() {
+                        report_output(sink.buffer.clone());
+                    }
+                    return;
+                }
+                InterpretResult::Cancelled => {
+                    let mut sink = session.output_sink.borrow_mut();
+                    sink.buffer += &format!(
+                        "[INFO] execution cancelled: exceeded the {:.1}s time limit\n",
+                        timeout_secs
+                    );
+                    if let Some(report_output) = sink.report_output.upgrade() {
+                        report_output(sink.buffer.clone());
+                    }
+                    return;
+                }
+                _ if is_entry => {
+                    if ends_in_expr_stmt {
+                        if let Some(value) = session.vm.last_value() {
+                            // The global chunk's last statement was an expression -- surface
+                            // the value it left on the stack the way a REPL echoes its result.
+                            report_value(format!("{}", value));
+                        }
+                    }
+
+                    let covered_lines = session.vm.covered_lines();
+                    let mut uncovered_lines: Vec<usize> =
+                        executable_lines.difference(covered_lines).copied().collect();
+                    uncovered_lines.sort_unstable();
+                    // Count against `executable_lines`, not `covered_lines.len()` -- the VM may
+                    // record lines outside the codegen's executable set, and the numerator must
+                    // share its base with `total`/`uncovered` or the three stop adding up.
+                    let covered = executable_lines.intersection(covered_lines).count();
+                    report_coverage(covered, executable_lines.len(), uncovered_lines);
+                }
+                _ => {}
+            },
+        }
+    }
+
+    let end = js_clock();
+    let mut sink = session.output_sink.borrow_mut();
+    sink.buffer += &format!("[INFO] Execution finished in {:.3} seconds\n", end - start);
+    if let Some(report_output) = sink.report_output.upgrade() {
+        report_output(sink.buffer.clone());
+    }
+    drop(sink);
+
+    let test_report = session.test_report.borrow();
+    if !test_report.is_empty() {
+        report_test_report(test_report.clone());
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Request {
-    ExecuteCode(String),
+    /// Evaluate a snippet against the agent's current session, keeping globals defined by
+    /// previous snippets alive. Interpretation is cancelled once `timeout_secs` elapses.
+    EvalLine { source: String, timeout_secs: f64 },
+    /// Clear the session, discarding all previously-defined globals.
+    Reset,
 }
 
-#[derive(Clone)]
 pub struct Runner {
     link: AgentLink<Self>,
+    session: Rc<RefCell<Session>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum RunResult {
     Stdout(String),
+    /// The value left over after evaluating the global chunk (e.g. a trailing expression),
+    /// rendered distinctly from stdout, the way a REPL echoes its result.
+    Value(String),
+    /// The `test(...)` outcomes collected while evaluating this snippet, if it called `test`
+    /// at least once. Not sent when the run ends in a top-level runtime error or is cancelled.
+    TestReport(TestReport),
+    /// Line coverage for the snippet that was just run: how many of its executable lines ran,
+    /// out of how many total, and which ones didn't.
+    Coverage {
+        covered: usize,
+        total: usize,
+        uncovered_lines: Vec<usize>,
+    },
     Error(String),
 }
 
@@ -181,26 +572,45 @@ impl Agent for Runner {
     type Output = RunResult;
 
     fn create(link: AgentLink<Self>) -> Self {
-        Self { link }
+        Self {
+            link,
+            session: Rc::new(RefCell::new(Session::new())),
+        }
     }
 
     fn update(&mut self, _msg: Self::Message) {}
 
     fn handle_input(&mut self, msg: Self::Input, id: HandlerId) {
         match msg {
-            Request::ExecuteCode(source) => {
-                let report_output = Rc::new(enc!(
-                    (self => runner, id) move |output: String| {
-                        runner
-                            .link
-                            .respond(id, RunResult::Stdout(output.to_string()))
-                    }
-                ));
-                let report_errors = Rc::new(|errors: String| {
-                    self.link.respond(id, RunResult::Error(errors.to_string()))
-                });
+            Request::EvalLine { source, timeout_secs } => {
+                let link = self.link.clone();
+                let report_output: Rc<dyn Fn(String)> =
+                    Rc::new(enc!((link) move |output: String| link.respond(id, RunResult::Stdout(output))));
+                let report_value =
+                    Rc::new(enc!((link) move |value: String| link.respond(id, RunResult::Value(value))));
+                let report_test_report =
+                    Rc::new(enc!((link) move |report: TestReport| link.respond(id, RunResult::TestReport(report))));
+                let report_coverage = Rc::new(enc!((link) move |covered: usize, total: usize, uncovered_lines: Vec<usize>| {
+                    link.respond(id, RunResult::Coverage { covered, total, uncovered_lines })
+                }));
+                let report_errors =
+                    Rc::new(enc!((link) move |errors: String| link.respond(id, RunResult::Error(errors))));
 
-                run(Rc::new(source), report_output, report_errors);
+                // Resolving `import`s is async (it fetches from `examples/`), so the whole
+                // compile/run pipeline runs inside a spawned future instead of inline here.
+                spawn_local(eval_with_imports(
+                    self.session.clone(),
+                    Rc::new(source),
+                    timeout_secs,
+                    report_output,
+                    report_value,
+                    report_test_report,
+                    report_coverage,
+                    report_errors,
+                ));
+            }
+            Request::Reset => {
+                self.session = Rc::new(RefCell::new(Session::new()));
             }
         }
     }